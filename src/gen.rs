@@ -11,6 +11,10 @@ pub struct Cmd {
     /// Type of output to generate
     #[clap(long, arg_enum)]
     r#output: Output,
+    /// Also embed the WASM bytecode and generate an `install`/`deploy`
+    /// helper that uploads it and returns a ready-to-use client
+    #[clap(long)]
+    deployer: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, ArgEnum)]
@@ -25,6 +29,8 @@ pub enum Error {
     GenerateRustFromFile(rust::GenerateFromFileError),
     #[error("format rust error: {0}")]
     FormatRustError(syn::Error),
+    #[error("read wasm file: {0}")]
+    ReadWasmFile(std::io::Error),
 }
 
 impl Cmd {
@@ -38,7 +44,10 @@ impl Cmd {
         let wasm_path_str = self.wasm.to_string_lossy();
         let code =
             rust::generate_from_file(&wasm_path_str, None).map_err(Error::GenerateRustFromFile)?;
-        let code_raw = code.to_string();
+        let mut code_raw = code.to_string();
+        if self.deployer {
+            code_raw.push_str(&self.generate_deployer()?);
+        }
         match syn::parse_file(&code_raw) {
             Ok(file) => {
                 let code_fmt = prettyplease::unparse(&file);
@@ -51,4 +60,56 @@ impl Cmd {
             }
         }
     }
+
+    /// Builds the extra items appended to the generated bindings when
+    /// `--deployer` is set.
+    fn generate_deployer(&self) -> Result<String, Error> {
+        let wasm = std::fs::read(&self.wasm).map_err(Error::ReadWasmFile)?;
+        let wasm_bytes: String = wasm.iter().map(|b| format!("\\x{:02x}", b)).collect();
+        Ok(format!(
+            "
+pub static CONTRACT_WASM: &[u8] = b\"{wasm_bytes}\";
+
+pub fn install(
+    host: &soroban_env_host::Host,
+) -> Result<[u8; 32], soroban_env_host::HostError> {{
+    use soroban_env_host::xdr::{{HostFunction, ScObject, ScVal}};
+    let args = vec![ScVal::Object(Some(ScObject::Bytes(
+        CONTRACT_WASM.try_into().expect(\"wasm too large\"),
+    )))]
+    .try_into()
+    .expect(\"failed to build host function args\");
+    let res = host.invoke_function(HostFunction::UploadContractWasm, args)?;
+    match res {{
+        ScVal::Object(Some(ScObject::Bytes(bytes))) => {{
+            let mut wasm_id = [0u8; 32];
+            wasm_id.copy_from_slice(bytes.as_slice());
+            Ok(wasm_id)
+        }}
+        _ => panic!(\"unexpected UploadContractWasm result\"),
+    }}
+}}
+
+pub fn deploy(
+    host: &soroban_env_host::Host,
+    contract_id: [u8; 32],
+) -> Result<Client, soroban_env_host::HostError> {{
+    use soroban_env_host::xdr::{{HostFunction, ScObject, ScVal}};
+    let wasm_id = install(host)?;
+    let args = vec![
+        ScVal::Object(Some(ScObject::Bytes(
+            contract_id.try_into().expect(\"invalid contract id\"),
+        ))),
+        ScVal::Object(Some(ScObject::Bytes(
+            wasm_id.try_into().expect(\"invalid wasm id\"),
+        ))),
+    ]
+    .try_into()
+    .expect(\"failed to build host function args\");
+    host.invoke_function(HostFunction::CreateContract, args)?;
+    Ok(Client::new(host, contract_id))
+}}
+"
+        ))
+    }
 }
@@ -1,17 +1,32 @@
-use std::{fmt::Debug, io, io::Cursor, net::SocketAddr, path::PathBuf, rc::Rc, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io,
+    io::Cursor,
+    net::SocketAddr,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
 use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
 use hex::FromHexError;
 use serde_json::{json, Value};
 use soroban_env_host::{
+    budget::{Budget, CostType},
     storage::Storage,
     xdr::{
         Error as XdrError, HostFunction, ReadXdr, ScHostStorageErrorCode, ScObject, ScStatus,
-        ScVal, WriteXdr,
+        ScVal, ScVmErrorCode, WriteXdr,
     },
     Host, HostError, Vm,
 };
-use warp::Filter;
+use tokio::sync::mpsc;
+use warp::{ws::Message, Filter};
 
 use crate::contractspec;
 use crate::jsonrpc;
@@ -22,11 +37,19 @@ use crate::utils;
 #[derive(Parser, Debug)]
 pub struct Cmd {
     /// Port to listen for requests on.
-    #[clap(long, default_value("8080"))]
+    #[clap(long, default_value("8080"), conflicts_with = "ipc_path")]
     port: u16,
+    /// Path to a Unix domain socket (or, on Windows, a named pipe) to listen
+    /// on instead of binding a TCP port. Each connection is framed as
+    /// newline-delimited JSON-RPC requests/responses.
+    #[clap(long, parse(from_os_str), conflicts_with = "port")]
+    ipc_path: Option<PathBuf>,
     /// File to persist ledger state
     #[clap(long, parse(from_os_str), default_value("ledger.json"))]
     ledger_file: PathBuf,
+    /// Don't compute and return budget/cost metrics for each `call`
+    #[clap(long)]
+    no_cost: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -63,104 +86,643 @@ enum Requests {
     },
 }
 
+/// A subscription id handed out by `subscribeEvents`.
+type SubscriptionId = u64;
+
+/// Which contract events a subscription wants to hear about. A `None`
+/// field matches anything.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+struct EventFilter {
+    contract_id: Option<String>,
+    topic: Option<String>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &ContractEvent) -> bool {
+        if let Some(contract_id) = &self.contract_id {
+            if contract_id != &event.contract_id {
+                return false;
+            }
+        }
+        if let Some(topic) = &self.topic {
+            if !event.topics.iter().any(|t| t.starts_with(topic.as_str())) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod event_filter_tests {
+    use super::*;
+
+    fn event(contract_id: &str, topics: &[&str]) -> ContractEvent {
+        ContractEvent {
+            contract_id: contract_id.to_string(),
+            topics: topics.iter().map(|t| t.to_string()).collect(),
+            data_json: "null".to_string(),
+            data_xdr: String::new(),
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = EventFilter::default();
+        assert!(filter.matches(&event("abc", &["foo"])));
+    }
+
+    #[test]
+    fn contract_id_must_match_exactly() {
+        let filter = EventFilter {
+            contract_id: Some("abc".to_string()),
+            topic: None,
+        };
+        assert!(filter.matches(&event("abc", &[])));
+        assert!(!filter.matches(&event("def", &[])));
+    }
+
+    #[test]
+    fn topic_matches_by_prefix() {
+        let filter = EventFilter {
+            contract_id: None,
+            topic: Some("transfer/".to_string()),
+        };
+        assert!(filter.matches(&event("abc", &["transfer/from"])));
+        assert!(!filter.matches(&event("abc", &["mint/to"])));
+    }
+}
+
+/// A contract event emitted during a single `invoke`.
+#[derive(Clone, Debug, serde::Serialize)]
+struct ContractEvent {
+    contract_id: String,
+    topics: Vec<String>,
+    data_json: String,
+    data_xdr: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[serde(untagged)]
+enum WsRequests {
+    // Tried first: `SubscribeEvents` has only defaulted fields and would
+    // otherwise match any params object, including an unsubscribe's.
+    Unsubscribe {
+        subscription: SubscriptionId,
+    },
+    SubscribeEvents {
+        #[serde(default)]
+        filter: EventFilter,
+    },
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionNotification {
+    jsonrpc: &'static str,
+    method: &'static str,
+    params: SubscriptionNotificationParams,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionNotificationParams {
+    subscription: SubscriptionId,
+    result: ContractEvent,
+}
+
+struct Subscription {
+    filter: EventFilter,
+    sender: mpsc::Sender<ContractEvent>,
+}
+
+/// Live event subscriptions, keyed by id.
+#[derive(Clone, Default)]
+struct Subscriptions(Arc<Mutex<HashMap<SubscriptionId, Subscription>>>);
+
+impl Subscriptions {
+    fn insert(&self, filter: EventFilter, sender: mpsc::Sender<ContractEvent>) -> SubscriptionId {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .lock()
+            .unwrap()
+            .insert(id, Subscription { filter, sender });
+        id
+    }
+
+    fn remove(&self, id: SubscriptionId) {
+        self.0.lock().unwrap().remove(&id);
+    }
+
+    fn broadcast(&self, events: &[ContractEvent]) {
+        let mut subscribers = self.0.lock().unwrap();
+        let mut closed = Vec::new();
+        for (id, sub) in subscribers.iter() {
+            for event in events {
+                if !sub.filter.matches(event) {
+                    continue;
+                }
+                // A full channel just drops this event for a slow
+                // consumer; only a closed one means the subscription
+                // itself is dead.
+                if let Err(mpsc::error::TrySendError::Closed(_)) =
+                    sub.sender.try_send(event.clone())
+                {
+                    closed.push(*id);
+                    break;
+                }
+            }
+        }
+        for id in closed {
+            subscribers.remove(&id);
+        }
+    }
+}
+
 impl Cmd {
     pub async fn run(&self) -> Result<(), Error> {
         let ledger_file = Arc::new(self.ledger_file.clone());
-        let with_ledger_file = warp::any().map(move || ledger_file.clone());
+        let subscriptions = Subscriptions::default();
+
+        let no_cost = self.no_cost;
+
+        if let Some(ipc_path) = &self.ipc_path {
+            return run_ipc(ipc_path, ledger_file, subscriptions, no_cost).await;
+        }
+
+        let with_ledger_file = warp::any().map({
+            let ledger_file = ledger_file.clone();
+            move || ledger_file.clone()
+        });
+        let with_subscriptions = warp::any().map({
+            let subscriptions = subscriptions.clone();
+            move || subscriptions.clone()
+        });
+        let with_no_cost = warp::any().map(move || no_cost);
 
         let call = warp::post()
             .and(warp::path("rpc"))
             .and(warp::body::json())
             .and(with_ledger_file)
+            .and(with_subscriptions.clone())
+            .and(with_no_cost)
             .map(
-                |request: jsonrpc::Request<Requests>, ledger_file: Arc<PathBuf>| {
-                    if request.jsonrpc != "2.0" {
-                        return json!({
-                            "jsonrpc": "2.0",
-                            "id": &request.id,
-                            "error": {
-                                "code":-32600,
-                                "message": "Invalid jsonrpc value in request",
-                            },
-                        })
-                        .to_string();
-                    }
-                    let result = match (request.method.as_str(), request.params) {
-                        (
-                            "call",
-                            Some(Requests::Call {
-                                id,
-                                func,
-                                args,
-                                args_xdr,
-                            }),
-                        ) => invoke(
-                            &id,
-                            &func,
-                            &args.unwrap_or_default(),
-                            &args_xdr.unwrap_or_default(),
-                            &ledger_file,
-                        ),
-                        _ => Err(Error::UnknownMethod),
-                    };
-                    let r = reply(&request.id, result);
-                    serde_json::to_string(&r).unwrap_or_else(|_| {
-                        json!({
-                            "jsonrpc": "2.0",
-                            "id": &request.id,
-                            "error": {
-                            "code":-32603,
-                            "message": "Internal server error",
-                            },
-                        })
-                        .to_string()
-                    })
+                |body: Value,
+                 ledger_file: Arc<PathBuf>,
+                 subscriptions: Subscriptions,
+                 no_cost: bool| {
+                    dispatch_body(body, &ledger_file, &subscriptions, no_cost)
+                        .map_or_else(String::new, |r| r.to_string())
                 },
             );
 
+        let ws = warp::path("ws")
+            .and(warp::ws())
+            .and(with_subscriptions)
+            .map(|ws: warp::ws::Ws, subscriptions: Subscriptions| {
+                ws.on_upgrade(move |socket| handle_ws(socket, subscriptions))
+            });
+
+        let routes = call.or(ws);
+
         let addr: SocketAddr = ([127, 0, 0, 1], self.port).into();
         println!("Listening on: {}", addr);
-        warp::serve(call).run(addr).await;
+        warp::serve(routes).run(addr).await;
         Ok(())
     }
 }
 
+/// Decodes one `/rpc` POST body, which per JSON-RPC 2.0 may be a single
+/// request object or a batch array of them, and dispatches each through
+/// [`handle_request`]. Returns `None` when there is nothing to send back
+/// (e.g. a lone notification).
+fn dispatch_body(
+    body: Value,
+    ledger_file: &PathBuf,
+    subscriptions: &Subscriptions,
+    no_cost: bool,
+) -> Option<Value> {
+    match &body {
+        Value::Array(requests) if requests.is_empty() => Some(json!({
+            "jsonrpc": "2.0",
+            "id": Value::Null,
+            "error": {
+                "code": -32600,
+                "message": "Invalid Request",
+            },
+        })),
+        Value::Array(requests) => {
+            let replies: Vec<Value> = requests
+                .iter()
+                .filter_map(|r| handle_request(r.clone(), ledger_file, subscriptions, no_cost))
+                .collect();
+            if replies.is_empty() {
+                None
+            } else {
+                Some(Value::Array(replies))
+            }
+        }
+        Value::Object(_) => handle_request(body, ledger_file, subscriptions, no_cost),
+        _ => Some(json!({
+            "jsonrpc": "2.0",
+            "id": Value::Null,
+            "error": {
+                "code": -32700,
+                "message": "Parse error",
+            },
+        })),
+    }
+}
+
+#[cfg(test)]
+mod dispatch_body_tests {
+    use super::*;
+
+    fn ledger_file() -> PathBuf {
+        PathBuf::from("unused.json")
+    }
+
+    #[test]
+    fn empty_batch_is_a_single_invalid_request_error() {
+        let subscriptions = Subscriptions::default();
+        let response = dispatch_body(json!([]), &ledger_file(), &subscriptions, true).unwrap();
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    #[test]
+    fn non_object_non_array_body_is_a_parse_error() {
+        let subscriptions = Subscriptions::default();
+        let response = dispatch_body(json!(42), &ledger_file(), &subscriptions, true).unwrap();
+        assert_eq!(response["error"]["code"], -32700);
+    }
+
+    #[test]
+    fn batch_omits_responses_for_notifications() {
+        let subscriptions = Subscriptions::default();
+        let body = json!([
+            {"jsonrpc": "2.0", "method": "foo", "id": "1"},
+            {"jsonrpc": "2.0", "method": "foo"},
+        ]);
+        let response = dispatch_body(body, &ledger_file(), &subscriptions, true).unwrap();
+        assert_eq!(response.as_array().unwrap().len(), 1);
+    }
+}
+
+/// Serves JSON-RPC over a Unix domain socket, framing each request/response
+/// as a single line of JSON.
+#[cfg(unix)]
+async fn run_ipc(
+    ipc_path: &std::path::Path,
+    ledger_file: Arc<PathBuf>,
+    subscriptions: Subscriptions,
+    no_cost: bool,
+) -> Result<(), Error> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // Only clear a stale socket left behind by a previous run, never a
+    // regular file the user happened to point --ipc-path at.
+    if let Ok(metadata) = std::fs::symlink_metadata(ipc_path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(ipc_path)?;
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(ipc_path)?;
+    println!("Listening on: {}", ipc_path.display());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let ledger_file = ledger_file.clone();
+        let subscriptions = subscriptions.clone();
+        tokio::spawn(async move {
+            if let Err(err) =
+                serve_ipc_connection(stream, ledger_file, subscriptions, no_cost).await
+            {
+                eprintln!("ipc connection closed: {}", err);
+            }
+        });
+    }
+}
+
+#[cfg(windows)]
+async fn run_ipc(
+    ipc_path: &std::path::Path,
+    ledger_file: Arc<PathBuf>,
+    subscriptions: Subscriptions,
+    no_cost: bool,
+) -> Result<(), Error> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    println!("Listening on: {}", ipc_path.display());
+    loop {
+        let server = ServerOptions::new().create(ipc_path)?;
+        server.connect().await?;
+        let ledger_file = ledger_file.clone();
+        let subscriptions = subscriptions.clone();
+        tokio::spawn(async move {
+            if let Err(err) =
+                serve_ipc_connection(server, ledger_file, subscriptions, no_cost).await
+            {
+                eprintln!("ipc connection closed: {}", err);
+            }
+        });
+    }
+}
+
+async fn serve_ipc_connection<S>(
+    stream: S,
+    ledger_file: Arc<PathBuf>,
+    subscriptions: Subscriptions,
+    no_cost: bool,
+) -> Result<(), Error>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let body: Value = match serde_json::from_str(&line) {
+            Ok(body) => body,
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": {
+                    "code": -32700,
+                    "message": err.to_string(),
+                },
+            }),
+        };
+        if let Some(response) = dispatch_body(body, &ledger_file, &subscriptions, no_cost) {
+            writer.write_all(response.to_string().as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+    Ok(())
+}
+
+/// Drives a single `/ws` connection until the client disconnects.
+async fn handle_ws(socket: warp::ws::WebSocket, subscriptions: Subscriptions) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    // Bounded so a slow client applies backpressure instead of letting a
+    // burst of events grow the channel without limit.
+    let (notify_tx, mut notify_rx) = mpsc::channel::<(SubscriptionId, ContractEvent)>(64);
+    let mut owned: Vec<SubscriptionId> = Vec::new();
+
+    loop {
+        tokio::select! {
+            incoming = ws_rx.next() => {
+                let Some(Ok(msg)) = incoming else { break };
+                if !msg.is_text() {
+                    continue;
+                }
+                let reply = match serde_json::from_str::<jsonrpc::Request<WsRequests>>(msg.to_str().unwrap_or_default()) {
+                    Ok(request) => match (request.method.as_str(), request.params) {
+                        ("subscribeEvents", Some(WsRequests::SubscribeEvents { filter })) => {
+                            let (event_tx, mut event_rx) = mpsc::channel(64);
+                            let id = subscriptions.insert(filter, event_tx);
+                            owned.push(id);
+                            let forward_tx = notify_tx.clone();
+                            tokio::spawn(async move {
+                                while let Some(event) = event_rx.recv().await {
+                                    if forward_tx.send((id, event)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            });
+                            Some(json!({
+                                "jsonrpc": "2.0",
+                                "id": request.id,
+                                "result": id,
+                            }))
+                        }
+                        ("unsubscribe", Some(WsRequests::Unsubscribe { subscription })) => {
+                            subscriptions.remove(subscription);
+                            owned.retain(|id| *id != subscription);
+                            Some(json!({
+                                "jsonrpc": "2.0",
+                                "id": request.id,
+                                "result": true,
+                            }))
+                        }
+                        _ => Some(json!({
+                            "jsonrpc": "2.0",
+                            "id": request.id,
+                            "error": {"code": -32601, "message": "Unknown method"},
+                        })),
+                    },
+                    Err(_) => Some(json!({
+                        "jsonrpc": "2.0",
+                        "id": Value::Null,
+                        "error": {"code": -32700, "message": "Parse error"},
+                    })),
+                };
+                if let Some(reply) = reply {
+                    if ws_tx.send(Message::text(reply.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            Some((id, event)) = notify_rx.recv() => {
+                let notification = SubscriptionNotification {
+                    jsonrpc: "2.0",
+                    method: "subscription",
+                    params: SubscriptionNotificationParams { subscription: id, result: event },
+                };
+                let Ok(text) = serde_json::to_string(&notification) else { continue };
+                if ws_tx.send(Message::text(text)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    for id in owned {
+        subscriptions.remove(id);
+    }
+}
+
+/// Handles a single decoded element of a `/rpc` POST body (which, per
+/// JSON-RPC 2.0, may itself be one element of a batch array). Returns
+/// `None` for notifications (requests with no `id`), which get no response
+/// at all.
+fn handle_request(
+    body: Value,
+    ledger_file: &PathBuf,
+    subscriptions: &Subscriptions,
+    no_cost: bool,
+) -> Option<Value> {
+    let request: jsonrpc::Request<Requests> = match serde_json::from_value(body) {
+        Ok(request) => request,
+        Err(err) => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": {
+                    "code": -32700,
+                    "message": err.to_string(),
+                },
+            }))
+        }
+    };
+    if request.id.is_none() {
+        return None;
+    }
+    if request.jsonrpc != "2.0" {
+        return Some(json!({
+            "jsonrpc": "2.0",
+            "id": &request.id,
+            "error": {
+                "code":-32600,
+                "message": "Invalid jsonrpc value in request",
+            },
+        }));
+    }
+    let result = match (request.method.as_str(), request.params) {
+        (
+            "call",
+            Some(Requests::Call {
+                id,
+                func,
+                args,
+                args_xdr,
+            }),
+        ) => invoke(
+            &id,
+            &func,
+            &args.unwrap_or_default(),
+            &args_xdr.unwrap_or_default(),
+            ledger_file,
+            no_cost,
+        )
+        .map(|(res, events, cost)| {
+            subscriptions.broadcast(&events);
+            (res, cost)
+        }),
+        _ => Err(Error::UnknownMethod),
+    };
+    let r = reply(&request.id, result);
+    Some(serde_json::to_value(&r).unwrap_or_else(|_| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": &request.id,
+            "error": {
+                "code": -32603,
+                "message": "Internal server error",
+            },
+        })
+    }))
+}
+
 fn reply(
     id: &Option<jsonrpc::Id>,
-    result: Result<ScVal, Error>,
+    result: Result<(ScVal, Option<Cost>), Error>,
 ) -> jsonrpc::Response<Value, Value> {
     match result {
-        Ok(res) => {
+        Ok((res, cost)) => {
             let mut ret_xdr_buf: Vec<u8> = Vec::new();
             match (
                 strval::to_string(&res),
                 res.write_xdr(&mut Cursor::new(&mut ret_xdr_buf)),
             ) {
-                (Ok(j), Ok(())) => jsonrpc::Response::Ok(jsonrpc::ResultResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: id.as_ref().unwrap_or(&jsonrpc::Id::Null).clone(),
-                    result: json!({
+                (Ok(j), Ok(())) => {
+                    let mut result = json!({
                         "json": j,
                         "xdr": base64::encode(ret_xdr_buf),
-                    }),
-                }),
+                    });
+                    if let Some(cost) = cost {
+                        result["cost"] = json!(cost);
+                    }
+                    jsonrpc::Response::Ok(jsonrpc::ResultResponse {
+                        jsonrpc: "2.0".to_string(),
+                        id: id.as_ref().unwrap_or(&jsonrpc::Id::Null).clone(),
+                        result,
+                    })
+                }
                 (Err(err), _) => reply(id, Err(Error::StrVal(err))),
                 (_, Err(err)) => reply(id, Err(Error::Xdr(err))),
             }
         }
-        Err(err) => jsonrpc::Response::Err(jsonrpc::ErrorResponse {
-            jsonrpc: "2.0".to_string(),
-            id: id.as_ref().unwrap_or(&jsonrpc::Id::Null).clone(),
-            error: jsonrpc::ErrorResponseError {
-                code: match err {
-                    Error::Serde(_) => -32700,
-                    Error::UnknownMethod => -32601,
-                    _ => -32603,
+        Err(err) => {
+            let (code, kind, details) = classify(&err);
+            jsonrpc::Response::Err(jsonrpc::ErrorResponse {
+                jsonrpc: "2.0".to_string(),
+                id: id.as_ref().unwrap_or(&jsonrpc::Id::Null).clone(),
+                error: jsonrpc::ErrorResponseError {
+                    code,
+                    message: err.to_string(),
+                    data: Some(json!({ "kind": kind, "details": details })),
                 },
-                message: err.to_string(),
-                data: None,
-            },
-        }),
+            })
+        }
+    }
+}
+
+/// Maps an `Error` to a stable class name and JSON-RPC error code.
+fn classify(err: &Error) -> (i64, String, Value) {
+    match err {
+        Error::Io(e) => (-32603, "Io".to_string(), json!(e.to_string())),
+        Error::StrVal(e) => (-32602, "InvalidParams".to_string(), json!(e.to_string())),
+        Error::Xdr(e) => (-32602, "InvalidXdr".to_string(), json!(e.to_string())),
+        Error::Host(e) => classify_host_error(e),
+        Error::Snapshot(e) => (-32603, "Snapshot".to_string(), json!(e.to_string())),
+        Error::Serde(_) => (-32700, "ParseError".to_string(), Value::Null),
+        Error::FromHex(e) => (-32602, "InvalidHex".to_string(), json!(e.to_string())),
+        Error::FunctionNotFoundInContractSpec => {
+            (-32602, "FunctionNotFound".to_string(), Value::Null)
+        }
+        Error::UnknownMethod => (-32601, "UnknownMethod".to_string(), Value::Null),
+    }
+}
+
+/// Classifies the `ScStatus` a `HostError` carries.
+fn classify_host_error(err: &HostError) -> (i64, String, Value) {
+    let status = &err.status;
+    let kind = format!("{:?}", status)
+        .split(['(', ' '])
+        .next()
+        .unwrap_or("Host")
+        .to_string();
+    match status {
+        ScStatus::HostStorageError(code) => (
+            -32001,
+            kind,
+            json!({ "storage_code": format!("{:?}", code) }),
+        ),
+        // Budget exhaustion (and other VM traps) surfaces as a VmError.
+        ScStatus::VmError(code) => (-32002, kind, json!({ "vm_code": format!("{:?}", code) })),
+        // The contract itself returned/trapped with this status code.
+        ScStatus::ContractError(code) => (-32003, kind, json!({ "contract_code": code })),
+        _ => (-32000, kind, json!({ "status": format!("{:?}", status) })),
+    }
+}
+
+/// Per-`CostType` budget consumption for a single `invoke`.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+struct Cost {
+    cpu_insns: u64,
+    mem_bytes: u64,
+    by_type: HashMap<String, u64>,
+}
+
+fn budget_cost(b: &Budget) -> Cost {
+    Cost {
+        cpu_insns: b.cpu_insns.get_count(),
+        mem_bytes: b.mem_bytes.get_count(),
+        by_type: CostType::variants()
+            .iter()
+            .map(|cost_type| (format!("{:?}", cost_type), b.get_input(*cost_type)))
+            .collect(),
+    }
+}
+
+fn cost_for(h: &Host, no_cost: bool) -> Option<Cost> {
+    if no_cost {
+        None
+    } else {
+        Some(h.get_budget(budget_cost))
     }
 }
 
@@ -170,7 +732,8 @@ fn invoke(
     args: &[Value],
     args_xdr: &[String],
     ledger_file: &PathBuf,
-) -> Result<ScVal, Error> {
+    no_cost: bool,
+) -> Result<(ScVal, Vec<ContractEvent>, Option<Cost>), Error> {
     let contract_id: [u8; 32] = utils::contract_id_from_str(contract)?;
 
     // Initialize storage and host
@@ -216,25 +779,102 @@ fn invoke(
 
     let res = h.invoke_function(HostFunction::Call, complete_args.try_into()?)?;
 
-    // TODO: Include costs in result struct
-    // let cost = h.get_budget(|b| {
-    //     let mut v = vec![
-    //         ("cpu_insns", b.cpu_insns.get_count()),
-    //         ("mem_bytes", b.mem_bytes.get_count()),
-    //     ];
-    //     // for cost_type in CostType::variants() {
-    //     //     v.push((cost_type.try_into()?, b.get_input(*cost_type)));
-    //     // }
-    //     Some(v)
-    // });
-
-    let (storage, _, _) = h.try_finish().map_err(|_h| {
+    let cost = cost_for(&h, no_cost);
+
+    let (storage, _, host_events) = h.try_finish().map_err(|_h| {
         HostError::from(ScStatus::HostStorageError(
             ScHostStorageErrorCode::UnknownError,
         ))
     })?;
 
+    let events = host_events
+        .into_iter()
+        .filter_map(|e| contract_event_from_host(&e))
+        .collect();
+
     snapshot::commit(ledger_entries, Some(&storage.map), ledger_file)?;
 
-    Ok(res)
+    Ok((res, events, cost))
+}
+
+/// Converts a raw host event into a [`ContractEvent`], skipping events
+/// that don't originate from a contract.
+fn contract_event_from_host(event: &soroban_env_host::events::HostEvent) -> Option<ContractEvent> {
+    let contract_id = hex::encode(event.contract_id?);
+    let topics = event
+        .topics
+        .iter()
+        .filter_map(|t| strval::to_string(t).ok())
+        .collect();
+    let data_json = strval::to_string(&event.data).ok()?;
+    let mut data_xdr_buf: Vec<u8> = Vec::new();
+    event
+        .data
+        .write_xdr(&mut Cursor::new(&mut data_xdr_buf))
+        .ok()?;
+    Some(ContractEvent {
+        contract_id,
+        topics,
+        data_json,
+        data_xdr: base64::encode(data_xdr_buf),
+    })
+}
+
+#[cfg(test)]
+mod cost_tests {
+    use super::*;
+
+    #[test]
+    fn no_cost_skips_budget_reporting() {
+        let h = Host::default();
+        assert!(cost_for(&h, true).is_none());
+    }
+
+    #[test]
+    fn cost_reporting_covers_every_cost_type() {
+        let h = Host::default();
+        let cost = cost_for(&h, false).unwrap();
+        assert_eq!(cost.by_type.len(), CostType::variants().len());
+    }
+}
+
+#[cfg(test)]
+mod classify_tests {
+    use super::*;
+
+    #[test]
+    fn classifies_non_host_errors_by_variant() {
+        assert_eq!(classify(&Error::UnknownMethod).0, -32601);
+        assert_eq!(
+            classify(&Error::FunctionNotFoundInContractSpec).1,
+            "FunctionNotFound"
+        );
+    }
+
+    #[test]
+    fn classifies_storage_errors_distinctly_from_generic_host_errors() {
+        let storage_err = HostError::from(ScStatus::HostStorageError(
+            ScHostStorageErrorCode::UnknownError,
+        ));
+        let (code, kind, _) = classify(&Error::Host(storage_err));
+        assert_eq!(code, -32001);
+        assert_eq!(kind, "HostStorageError");
+    }
+
+    #[test]
+    fn classifies_budget_exhaustion_as_a_vm_error() {
+        let vm_err = HostError::from(ScStatus::VmError(ScVmErrorCode::TrapCpuLimitExceeded));
+        let (code, kind, _) = classify(&Error::Host(vm_err));
+        assert_eq!(code, -32002);
+        assert_eq!(kind, "VmError");
+    }
+
+    #[test]
+    fn classifies_contract_errors_distinctly_from_storage_errors() {
+        let contract_err = HostError::from(ScStatus::ContractError(7));
+        let (code, kind, details) = classify(&Error::Host(contract_err));
+        assert_eq!(code, -32003);
+        assert_eq!(kind, "ContractError");
+        assert_eq!(details["contract_code"], 7);
+    }
 }